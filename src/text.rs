@@ -1,12 +1,13 @@
 //! OpenGL text rendering.
 
 use std::borrow::Cow;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
+use std::ffi::CStr;
 use std::result::Result as StdResult;
-use std::{cmp, mem, ptr};
+use std::{fmt, iter, mem, ptr};
 
+use bitflags::bitflags;
 use crossfont::{
     BitmapBuffer, FontDesc, FontKey, GlyphKey, Metrics, Rasterize, RasterizedGlyph,
     Rasterizer, Size as FontSize, Slant, Style, Weight,
@@ -15,47 +16,146 @@ use tiny_skia::{Pixmap, Transform};
 use usvg::{FitTo, Options, Tree};
 
 use crate::gl;
-use crate::gl::types::GLuint;
+use crate::gl::types::{GLenum, GLuint};
 
 /// Width and height of the glyph atlas texture.
 const ATLAS_SIZE: i32 = 1024;
 
+/// Maximum number of entries kept in the glyph/SVG cache before the least
+/// recently used one is evicted.
+const CACHE_CAPACITY: usize = 1024;
+
 /// Convenience result wrapper.
 type Result<T> = StdResult<T, Box<dyn Error>>;
 
+bitflags! {
+    /// Emphasis requested for a rasterized glyph.
+    #[derive(Default)]
+    pub struct Flags: u8 {
+        const BOLD = 0b01;
+        const ITALIC = 0b10;
+    }
+}
+
+/// A font family's regular/bold/italic/bold-italic faces, indexed by
+/// [`Flags::bits`].
+struct FontStyles {
+    keys: [FontKey; 4],
+    /// Whether the bold style resolved to a `FontKey` distinct from the
+    /// regular face, i.e. fontconfig matched a real bold face rather than
+    /// substituting regular (fontconfig's `load_font` returns `Ok` either
+    /// way, so success alone can't tell the two apart).
+    has_bold: bool,
+    /// Whether the italic style resolved to a `FontKey` distinct from the
+    /// regular face, as opposed to a substituted regular face.
+    has_italic: bool,
+    /// Whether the bold-italic style resolved to a `FontKey` distinct from
+    /// the regular face, as opposed to a substituted regular face.
+    has_bold_italic: bool,
+}
+
 /// Cached OpenGL rasterization.
 pub struct GlRasterizer {
     // OpenGL subtexture caching.
-    cache: HashMap<CacheKey, GlSubTexture>,
+    cache: LruCache,
     atlas: Atlas,
 
     // FreeType font rasterization.
     rasterizer: Rasterizer,
     size: FontSize,
-    font: FontKey,
+    /// Primary font at index `0`, followed by the fallback chain in probe
+    /// order; each entry carries its own style variants.
+    fonts: Vec<FontStyles>,
+    /// Memoized `character -> fonts` index, so repeated rasterization of the
+    /// same missing-in-primary character skips re-probing the whole chain.
+    resolved_fonts: HashMap<char, usize>,
+
+    /// GL flavor detected from the current context, so the rendering side
+    /// can pick matching GLSL ES or desktop GLSL shader sources.
+    gl_flavor: GlFlavor,
 }
 
 impl GlRasterizer {
-    pub fn new(font: &str, size: impl Into<FontSize>) -> Result<Self> {
+    /// Create a new rasterizer.
+    ///
+    /// `fallbacks` is probed in order whenever `font` has no coverage for a
+    /// character, so e.g. emoji or CJK fonts can be layered behind a primary
+    /// monospace font. Every font in the chain loads its regular, bold,
+    /// italic and bold-italic faces up front.
+    pub fn new(font: &str, fallbacks: &[&str], size: impl Into<FontSize>) -> Result<Self> {
         let size = size.into();
 
         // Create FreeType rasterizer.
         let mut rasterizer = Rasterizer::new(1.)?;
 
-        // Load font at the requested size.
-        let font_style = Style::Description { slant: Slant::Normal, weight: Weight::Normal };
-        let font_desc = FontDesc::new(font, font_style);
-        let font = rasterizer.load_font(&font_desc, size)?;
+        // Load the primary font and its fallback chain at the requested size.
+        let mut fonts = Vec::with_capacity(fallbacks.len() + 1);
+        for name in iter::once(font).chain(fallbacks.iter().copied()) {
+            fonts.push(Self::load_styles(&mut rasterizer, name, size)?);
+        }
+
+        // The GL context must already be current at this point, so the
+        // reported version string reflects what the atlas will be uploaded
+        // to.
+        let gl_flavor = GlFlavor::detect();
 
         Ok(Self {
             rasterizer,
-            font,
+            fonts,
             size,
-            atlas: Default::default(),
-            cache: Default::default(),
+            atlas: Atlas::new(gl_flavor),
+            cache: LruCache::new(CACHE_CAPACITY),
+            resolved_fonts: HashMap::new(),
+            gl_flavor,
         })
     }
 
+    /// Get the GL flavor detected for the current context.
+    ///
+    /// Callers use this to pick GLSL ES vs desktop GLSL shader sources to
+    /// match the texture formats the atlas uploads under this flavor.
+    pub fn gl_flavor(&self) -> GlFlavor {
+        self.gl_flavor
+    }
+
+    /// Load the regular/bold/italic/bold-italic faces for one font family.
+    ///
+    /// Styles fontconfig can't match fall back to the regular face, so
+    /// [`Self::synthesize`] can emulate the missing emphasis instead. A face
+    /// only counts as "real" when its resolved `FontKey` differs from the
+    /// regular face's, since fontconfig's `load_font` returns `Ok` even when
+    /// it silently substitutes regular for a missing bold/italic/bold-italic
+    /// description.
+    fn load_styles(rasterizer: &mut Rasterizer, name: &str, size: FontSize) -> Result<FontStyles> {
+        let regular = Self::load_face(rasterizer, name, size, Slant::Normal, Weight::Normal)?;
+        let bold = Self::load_face(rasterizer, name, size, Slant::Normal, Weight::Bold);
+        let italic = Self::load_face(rasterizer, name, size, Slant::Italic, Weight::Normal);
+        let bold_italic = Self::load_face(rasterizer, name, size, Slant::Italic, Weight::Bold);
+
+        let has_bold = bold.as_ref().is_ok_and(|&key| key != regular);
+        let has_italic = italic.as_ref().is_ok_and(|&key| key != regular);
+        let has_bold_italic = bold_italic.as_ref().is_ok_and(|&key| key != regular);
+
+        let mut keys = [regular; 4];
+        keys[Flags::BOLD.bits() as usize] = bold.unwrap_or(regular);
+        keys[Flags::ITALIC.bits() as usize] = italic.unwrap_or(regular);
+        keys[(Flags::BOLD | Flags::ITALIC).bits() as usize] = bold_italic.unwrap_or(regular);
+
+        Ok(FontStyles { keys, has_bold, has_italic, has_bold_italic })
+    }
+
+    /// Load a single `(slant, weight)` face of a font family.
+    fn load_face(
+        rasterizer: &mut Rasterizer,
+        name: &str,
+        size: FontSize,
+        slant: Slant,
+        weight: Weight,
+    ) -> Result<FontKey> {
+        let font_desc = FontDesc::new(name, Style::Description { slant, weight });
+        Ok(rasterizer.load_font(&font_desc, size)?)
+    }
+
     /// Rasterize each glyph in a string.
     ///
     /// Returns an iterator over all glyphs. The advance stored on each glyph
@@ -67,11 +167,22 @@ impl GlRasterizer {
         &'a mut self,
         text: &'a str,
     ) -> impl Iterator<Item = GlSubTexture> + 'a {
-        text.chars().scan(self.glyph_key(' '), |glyph_key, c| {
-            let mut glyph = self.rasterize_char(c).ok()?;
+        self.rasterize_string_styled(text, Flags::empty())
+    }
+
+    /// Rasterize each glyph in a string with the given emphasis applied.
+    ///
+    /// Otherwise identical to [`Self::rasterize_string`].
+    pub fn rasterize_string_styled<'a>(
+        &'a mut self,
+        text: &'a str,
+        flags: Flags,
+    ) -> impl Iterator<Item = GlSubTexture> + 'a {
+        text.chars().scan(self.glyph_key(' ', flags), move |glyph_key, c| {
+            let mut glyph = self.rasterize_char_styled(c, flags).ok()?;
 
             // Add kerning to glyph advance.
-            let last_key = mem::replace(glyph_key, self.glyph_key(c));
+            let last_key = mem::replace(glyph_key, self.glyph_key(c, flags));
             let kerning = self.rasterizer.kerning(last_key, *glyph_key);
             glyph.advance.0 += kerning.0 as i32;
             glyph.advance.1 += kerning.1 as i32;
@@ -82,28 +193,47 @@ impl GlRasterizer {
 
     /// Get rasterized OpenGL glyph.
     pub fn rasterize_char(&mut self, character: char) -> Result<GlSubTexture> {
-        let glyph_key = self.glyph_key(character);
+        self.rasterize_char_styled(character, Flags::empty())
+    }
+
+    /// Get a rasterized OpenGL glyph with the given emphasis applied.
+    ///
+    /// Otherwise identical to [`Self::rasterize_char`].
+    pub fn rasterize_char_styled(&mut self, character: char, flags: Flags) -> Result<GlSubTexture> {
+        let key = CacheKey::Character(character, flags);
 
         // Try to load glyph from cache.
-        let entry = match self.cache.entry(character.into()) {
-            Entry::Occupied(entry) => return Ok(*entry.get()),
-            Entry::Vacant(entry) => entry,
+        if let Some(glyph) = self.cache.get(&key) {
+            return Ok(glyph);
+        }
+
+        // Rasterize the glyph if it's missing, walking the fallback chain.
+        let rasterized_glyph = self.rasterize_with_fallback(character, flags)?;
+        let glyph = match self.atlas.insert(&rasterized_glyph) {
+            Ok(glyph) => glyph,
+            Err(AtlasFull) => {
+                // Evict the whole working set and retry once on a clean atlas.
+                self.atlas.clear();
+                self.cache.clear();
+                self.atlas.insert(&rasterized_glyph)?
+            },
         };
 
-        // Rasterize the glyph if it's missing.
-        let rasterized_glyph = self.rasterizer.get_glyph(glyph_key)?;
-        let glyph = self.atlas.insert(&rasterized_glyph)?;
+        if let Some(evicted) = self.cache.insert(key, glyph) {
+            self.atlas.deallocate(evicted);
+        }
 
-        Ok(*entry.insert(glyph))
+        Ok(glyph)
     }
 
     /// Rasterize an SVG from its text.
     pub fn rasterize_svg(&mut self, svg: Svg) -> Result<GlSubTexture> {
+        let key = CacheKey::from(svg);
+
         // Try to lead svg from cache.
-        let entry = match self.cache.entry(svg.into()) {
-            Entry::Occupied(entry) => return Ok(*entry.get()),
-            Entry::Vacant(entry) => entry,
-        };
+        if let Some(glyph) = self.cache.get(&key) {
+            return Ok(glyph);
+        }
 
         let (width, height) = svg.size();
 
@@ -119,133 +249,363 @@ impl GlRasterizer {
 
         // Load SVG into atlas.
         let atlas_entry = AtlasEntry::new_svg(pixmap.take(), width, height);
-        let svg = self.atlas.insert(atlas_entry)?;
+        let glyph = match self.atlas.insert(atlas_entry.clone()) {
+            Ok(glyph) => glyph,
+            Err(AtlasFull) => {
+                // Evict the whole working set and retry once on a clean atlas.
+                self.atlas.clear();
+                self.cache.clear();
+                self.atlas.insert(atlas_entry)?
+            },
+        };
+
+        if let Some(evicted) = self.cache.insert(key, glyph) {
+            self.atlas.deallocate(evicted);
+        }
 
-        Ok(*entry.insert(svg))
+        Ok(glyph)
     }
 
     /// Get font metrics.
     pub fn metrics(&self) -> Result<Metrics> {
-        Ok(self.rasterizer.metrics(self.font, self.size)?)
+        Ok(self.rasterizer.metrics(self.fonts[0].keys[0], self.size)?)
+    }
+
+    /// Get glyph key for a character and style, using whichever font it was
+    /// last resolved to, or the primary font if it hasn't been rasterized
+    /// yet.
+    fn glyph_key(&self, character: char, flags: Flags) -> GlyphKey {
+        let font_index = self.resolved_fonts.get(&character).copied().unwrap_or(0);
+        let font_key = self.fonts[font_index].keys[flags.bits() as usize];
+        GlyphKey { font_key, size: self.size, character }
+    }
+
+    /// Rasterize a character, walking the fallback font chain in order and
+    /// keeping the first face that produces a non-empty glyph, with the
+    /// requested style applied (synthesized if no face has it natively).
+    ///
+    /// If no face produces a non-empty glyph, the first empty result in chain
+    /// order is returned instead, which draws nothing; an error is returned
+    /// only when every face fails to rasterize the character.
+    fn rasterize_with_fallback(&mut self, character: char, flags: Flags) -> Result<RasterizedGlyph> {
+        if let Some(&font_index) = self.resolved_fonts.get(&character) {
+            let styles = &self.fonts[font_index];
+            let glyph_key =
+                GlyphKey { font_key: styles.keys[flags.bits() as usize], size: self.size, character };
+            let mut glyph = self.rasterizer.get_glyph(glyph_key)?;
+            Self::synthesize(&mut glyph, flags, styles);
+            return Ok(glyph);
+        }
+
+        let mut replacement = None;
+        for (font_index, styles) in self.fonts.iter().enumerate() {
+            let glyph_key =
+                GlyphKey { font_key: styles.keys[flags.bits() as usize], size: self.size, character };
+            let mut glyph = match self.rasterizer.get_glyph(glyph_key) {
+                Ok(glyph) => glyph,
+                Err(_) => continue,
+            };
+
+            if glyph.width > 0 && glyph.height > 0 {
+                self.resolved_fonts.insert(character, font_index);
+                Self::synthesize(&mut glyph, flags, styles);
+                return Ok(glyph);
+            }
+
+            replacement.get_or_insert(glyph);
+        }
+
+        replacement.ok_or_else(|| "no font in the fallback chain could rasterize glyph".into())
+    }
+
+    /// Apply synthetic bold/italic emphasis to a glyph rasterized from a
+    /// face that has no dedicated face for the requested style.
+    ///
+    /// A combined bold-italic request is checked against
+    /// [`FontStyles::has_bold_italic`] rather than the two styles
+    /// individually: `keys[BOLD | ITALIC]` falls back straight to the
+    /// regular face whenever there is no dedicated bold-italic face, even if
+    /// standalone bold and italic faces exist, so both emphases must be
+    /// synthesized together in that case.
+    fn synthesize(glyph: &mut RasterizedGlyph, flags: Flags, styles: &FontStyles) {
+        let (width, height) = (glyph.width, glyph.height);
+        let (buffer, channels) = match &mut glyph.buffer {
+            BitmapBuffer::Rgb(buffer) => (buffer, 3),
+            BitmapBuffer::Rgba(buffer) => (buffer, 4),
+        };
+
+        if flags.contains(Flags::BOLD | Flags::ITALIC) {
+            if !styles.has_bold_italic {
+                embolden(buffer, width, height, channels);
+                shear(buffer, width, height, channels);
+            }
+            return;
+        }
+
+        if flags.contains(Flags::BOLD) && !styles.has_bold {
+            embolden(buffer, width, height, channels);
+        }
+        if flags.contains(Flags::ITALIC) && !styles.has_italic {
+            shear(buffer, width, height, channels);
+        }
+    }
+}
+
+/// Thicken a coverage/color bitmap in place by dilating every pixel with its
+/// right and bottom neighbors, used to emulate a bold face.
+fn embolden(buffer: &mut [u8], width: i32, height: i32, channels: i32) {
+    let (width, height, channels) = (width as usize, height as usize, channels as usize);
+    let original = buffer.to_vec();
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut value = [0u8; 4];
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1)] {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx >= width || ny >= height {
+                    continue;
+                }
+
+                let offset = (ny * width + nx) * channels;
+                for c in 0..channels {
+                    value[c] = value[c].max(original[offset + c]);
+                }
+            }
+
+            let offset = (y * width + x) * channels;
+            buffer[offset..offset + channels].copy_from_slice(&value[..channels]);
+        }
     }
+}
+
+/// Shear a coverage/color bitmap in place, shifting rows further right the
+/// higher up the glyph they sit, used to emulate an italic face.
+fn shear(buffer: &mut [u8], width: i32, height: i32, channels: i32) {
+    let (width, height, channels) = (width as usize, height as usize, channels as usize);
+    let original = buffer.to_vec();
+    buffer.fill(0);
 
-    /// Get glyph key for a character.
-    fn glyph_key(&self, character: char) -> GlyphKey {
-        GlyphKey { font_key: self.font, size: self.size, character }
+    for y in 0..height {
+        let shift = (height - y) / 4;
+        for x in 0..width {
+            let src_x = match x.checked_sub(shift) {
+                Some(src_x) if src_x < width => src_x,
+                _ => continue,
+            };
+
+            let src = (y * width + src_x) * channels;
+            let dst = (y * width + x) * channels;
+            buffer[dst..dst + channels].copy_from_slice(&original[src..src + channels]);
+        }
     }
 }
 
 /// Atlas for combining multiple textures in OpenGL.
 ///
-/// The strategy for filling an atlas looks roughly like this:
+/// Glyphs are split across two texture families by [`ContentType`]: a
+/// single-channel `GL_RED` mask atlas for ordinary text coverage, and a
+/// `GL_RGBA` color atlas for multicolor glyphs and SVGs. This keeps the much
+/// more common monochrome glyphs at a quarter of the memory cost of the
+/// color path.
+///
+/// Within a family, space is handed out by a shelf packer: each texture
+/// keeps a list of shelves, every shelf has a baseline `y`, a `height` and a
+/// fill cursor `x`. Allocating a `width x height` rect picks the shortest
+/// shelf that is still tall and wide enough, or opens a new shelf at the top
+/// of the texture when none fits:
 ///
 /// ```text
 ///                           (width, height)
 ///   ?????????????????????????????????????????????????????????????????????????????????????????????
-///   ??? 10  ???     ???     ???     ???     ??? <- Atlas is full when next glyph's height doesn't fit.
+///   ??? 10  ???     ???     ???     ???     ??? <- New shelf opened at the top.
 ///   ???     ???     ???     ???     ???     ??? <- Empty spaces for new elements.
 ///   ?????????????????????????????????????????????????????????????????????????????????????????????
 ///   ??? 5   ??? 6   ??? 7   ??? 8   ??? 9   ???
 ///   ???     ???     ???     ???     ???     ???
-///   ????????????????????????????????????????????????????????????????????????????????????????????? <- Row height is tallest subtexture in the row.
-///   ??? 1   ??? 2   ??? 3   ??? 4         ???    This is the baseline for the next row.
-///   ???     ???     ???     ???           ??? <- Row is full when next glyph's width doesn't fit.
+///   ????????????????????????????????????????????????????????????????????????????????????????????? <- Shelf height is tallest subtexture in it.
+///   ??? 1   ??? 2   ??? 3   ??? 4         ???    This is the baseline for the next shelf.
+///   ???     ???     ???     ???           ??? <- Shelf is full when next glyph's width doesn't fit.
 ///   ?????????????????????????????????????????????????????????????????????????????????????????????
 /// (0, 0)
 /// ```
+///
+/// Allocations can be freed again through [`Atlas::deallocate`]; once a
+/// shelf loses its last allocation and sits at the top of its texture, it is
+/// dropped so its space can be handed out again.
 pub struct Atlas {
-    /// OpenGL texture ID.
-    textures: Vec<GLuint>,
-    /// Largest glyph's height in this row.
-    row_height: i32,
-    /// X position for writing new glyphs.
-    cursor_x: i32,
-    /// Y position for writing new glyphs.
-    cursor_y: i32,
+    /// Single-channel coverage masks for monochrome text glyphs.
+    mask: AtlasFamily,
+    /// Full RGBA color data for multicolor glyphs and SVGs.
+    color: AtlasFamily,
 }
 
-impl Default for Atlas {
-    fn default() -> Self {
+impl Atlas {
+    /// Create an atlas whose textures are compatible with the given GL
+    /// flavor.
+    fn new(flavor: GlFlavor) -> Self {
         Self {
-            textures: vec![Self::create_texture()],
-            row_height: Default::default(),
-            cursor_x: Default::default(),
-            cursor_y: Default::default(),
+            mask: AtlasFamily::new(ContentType::Mask, flavor),
+            color: AtlasFamily::new(ContentType::Color, flavor),
+        }
+    }
+
+    /// Insert an entry into the atlas, routing it to the mask or color
+    /// texture family based on [`AtlasEntry::multicolor`].
+    fn insert<'a, E: Into<AtlasEntry<'a>>>(
+        &mut self,
+        entry: E,
+    ) -> StdResult<GlSubTexture, AtlasFull> {
+        let entry = entry.into();
+        let family = if entry.multicolor { &mut self.color } else { &mut self.mask };
+        family.insert(entry)
+    }
+
+    /// Free a previously inserted entry's atlas space.
+    fn deallocate(&mut self, alloc_id: AllocId) {
+        match alloc_id.content_type {
+            ContentType::Mask => self.mask.deallocate(alloc_id),
+            ContentType::Color => self.color.deallocate(alloc_id),
         }
     }
+
+    /// Delete all but the first texture of each family and reset their
+    /// allocation state, freeing the entire working set in one go.
+    ///
+    /// Every [`AllocId`] handed out before calling this is invalidated;
+    /// callers must drop all cached entries referencing this atlas too.
+    fn clear(&mut self) {
+        self.mask.clear();
+        self.color.clear();
+    }
 }
 
-impl Drop for Atlas {
+/// One [`ContentType`]'s worth of atlas textures, with its own shelf-packed
+/// allocation bookkeeping.
+struct AtlasFamily {
+    content_type: ContentType,
+    /// GL flavor the textures in this family were created for.
+    flavor: GlFlavor,
+    /// Textures backing this family, each with its own shelves.
+    textures: Vec<AtlasTexture>,
+    /// Bookkeeping for outstanding allocations, keyed by [`AllocId::index`].
+    allocations: HashMap<usize, Allocation>,
+    /// Monotonic counter used to mint new [`AllocId`]s.
+    next_id: usize,
+}
+
+impl Drop for AtlasFamily {
     fn drop(&mut self) {
         for texture in &self.textures {
             unsafe {
-                gl::DeleteTextures(1, texture);
+                gl::DeleteTextures(1, &texture.id);
             }
         }
     }
 }
 
-impl Atlas {
-    /// Insert an entry into the atlas.
-    fn insert<'a, E: Into<AtlasEntry<'a>>>(&mut self, entry: E) -> Result<GlSubTexture> {
-        let entry = entry.into();
-
-        // Error if entry cannot fit at all.
-        if self.cursor_x > ATLAS_SIZE || self.cursor_y > ATLAS_SIZE {
-            return Err("atlas is full".into());
+impl AtlasFamily {
+    fn new(content_type: ContentType, flavor: GlFlavor) -> Self {
+        Self {
+            content_type,
+            flavor,
+            textures: vec![AtlasTexture::new(content_type, flavor)],
+            allocations: HashMap::new(),
+            next_id: 0,
         }
+    }
 
-        // Create new row if entry doesn't fit into current one.
-        if self.cursor_x + entry.width > ATLAS_SIZE {
-            self.cursor_y += mem::take(&mut self.row_height);
-            self.cursor_x = 0;
+    /// Insert an entry into this family's textures.
+    fn insert(&mut self, entry: AtlasEntry<'_>) -> StdResult<GlSubTexture, AtlasFull> {
+        // Error if entry could never fit into an empty atlas.
+        if entry.width > ATLAS_SIZE || entry.height > ATLAS_SIZE {
+            return Err(AtlasFull);
         }
 
-        // Create a new texture if the row's available height is too little.
-        if self.cursor_y + entry.height > ATLAS_SIZE {
-            self.textures.push(Self::create_texture());
-            self.row_height = 0;
-            self.cursor_x = 0;
-            self.cursor_y = 0;
-        }
+        // Reuse space in any existing texture, including space freed again.
+        let allocation = self.textures.iter_mut().enumerate().find_map(|(i, texture)| {
+            Self::allocate(texture, &entry).map(|(shelf_index, x, y)| (i, shelf_index, x, y))
+        });
+        let (texture_index, shelf_index, x, y) =
+            match allocation {
+                Some(allocation) => allocation,
+                None => {
+                    // Open a new texture once no existing one has room left.
+                    self.textures.push(AtlasTexture::new(self.content_type, self.flavor));
+                    let active_texture = self.textures.len() - 1;
+                    let (shelf_index, x, y) =
+                        Self::allocate(&mut self.textures[active_texture], &entry)
+                            .ok_or(AtlasFull)?;
+                    (active_texture, shelf_index, x, y)
+                },
+            };
 
         // Upload entry's buffer to OpenGL.
-        let active_texture = self.textures[self.textures.len() - 1];
+        let texture = &mut self.textures[texture_index];
+        let format = self.content_type.gl_format(self.flavor);
         unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, active_texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
 
-            gl::TexSubImage2D(
-                gl::TEXTURE_2D,
-                0,
-                self.cursor_x,
-                self.cursor_y,
-                entry.width,
-                entry.height,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
-                entry.buffer.as_ptr() as *const _,
-            );
+            match &mut texture.cpu_buffer {
+                // Some GLES2 drivers don't reliably support partial
+                // `TexSubImage2D` uploads, so stitch the entry into a CPU
+                // mirror of the whole texture and re-upload it in one shot.
+                Some(cpu_buffer) => {
+                    blit(cpu_buffer, self.content_type.channels(), x, y, entry.width, &entry.buffer);
+
+                    gl::TexImage2D(
+                        gl::TEXTURE_2D,
+                        0,
+                        format as i32,
+                        ATLAS_SIZE,
+                        ATLAS_SIZE,
+                        0,
+                        format,
+                        gl::UNSIGNED_BYTE,
+                        cpu_buffer.as_ptr() as *const _,
+                    );
+                },
+                None => {
+                    gl::TexSubImage2D(
+                        gl::TEXTURE_2D,
+                        0,
+                        x,
+                        y,
+                        entry.width,
+                        entry.height,
+                        format,
+                        gl::UNSIGNED_BYTE,
+                        entry.buffer.as_ptr() as *const _,
+                    );
+                },
+            }
 
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
 
+        let texture_id = texture.id;
+
         // Generate UV coordinates.
-        let uv_bot = self.cursor_y as f32 / ATLAS_SIZE as f32;
-        let uv_left = self.cursor_x as f32 / ATLAS_SIZE as f32;
+        let uv_bot = y as f32 / ATLAS_SIZE as f32;
+        let uv_left = x as f32 / ATLAS_SIZE as f32;
         let uv_height = entry.height as f32 / ATLAS_SIZE as f32;
         let uv_width = entry.width as f32 / ATLAS_SIZE as f32;
 
-        // Update atlas write position.
-        self.row_height = cmp::max(self.row_height, entry.height);
-        self.cursor_x += entry.width;
+        let index = self.next_id;
+        self.next_id += 1;
+        self.allocations.insert(
+            index,
+            Allocation { texture: texture_index, shelf: shelf_index, width: entry.width },
+        );
+        let alloc_id = AllocId { content_type: self.content_type, index };
 
         Ok(GlSubTexture {
             uv_height,
             uv_width,
             uv_left,
             uv_bot,
-            multicolor: entry.multicolor,
-            texture_id: active_texture,
+            alloc_id,
+            content_type: self.content_type,
+            texture_id,
             advance: entry.advance,
             height: entry.height as i16,
             width: entry.width as i16,
@@ -254,8 +614,102 @@ impl Atlas {
         })
     }
 
-    /// Create a new atlas texture.
-    fn create_texture() -> GLuint {
+    /// Free a previously inserted entry's atlas space.
+    ///
+    /// If this empties the topmost shelf(s) of its texture, their space is
+    /// reclaimed so it can be handed out to future allocations again.
+    fn deallocate(&mut self, alloc_id: AllocId) {
+        let allocation = match self.allocations.remove(&alloc_id.index) {
+            Some(allocation) => allocation,
+            None => return,
+        };
+
+        let texture = &mut self.textures[allocation.texture];
+        if let Some(shelf) = texture.shelves.get_mut(allocation.shelf) {
+            shelf.allocations -= 1;
+        }
+
+        while texture.shelves.last().is_some_and(|shelf| shelf.allocations == 0) {
+            texture.shelves.pop();
+        }
+    }
+
+    /// Delete all but the first atlas texture and reset its allocation
+    /// state, freeing the entire working set in one go.
+    fn clear(&mut self) {
+        for texture in self.textures.drain(1..) {
+            unsafe {
+                gl::DeleteTextures(1, &texture.id);
+            }
+        }
+
+        self.textures[0].shelves.clear();
+        self.allocations.clear();
+        self.next_id = 0;
+    }
+
+    /// Try to place a `width x height` rect into an existing shelf, opening
+    /// a new one at the top of the texture if none has room.
+    ///
+    /// Returns the shelf's index together with the `(x, y)` position the
+    /// entry was placed at.
+    fn allocate(texture: &mut AtlasTexture, entry: &AtlasEntry<'_>) -> Option<(usize, i32, i32)> {
+        // Pick the shortest shelf that is tall and wide enough.
+        let mut best = None;
+        for (i, shelf) in texture.shelves.iter().enumerate() {
+            let has_room = shelf.height >= entry.height && ATLAS_SIZE - shelf.cursor_x >= entry.width;
+            let is_better = best.is_none_or(|b: usize| shelf.height < texture.shelves[b].height);
+            if has_room && is_better {
+                best = Some(i);
+            }
+        }
+
+        let shelf_index = match best {
+            Some(i) => i,
+            None => {
+                let y: i32 = texture.shelves.iter().map(|shelf| shelf.height).sum();
+                if y + entry.height > ATLAS_SIZE {
+                    return None;
+                }
+                texture.shelves.push(Shelf { y, height: entry.height, cursor_x: 0, allocations: 0 });
+                texture.shelves.len() - 1
+            },
+        };
+
+        let shelf = &mut texture.shelves[shelf_index];
+        let (x, y) = (shelf.cursor_x, shelf.y);
+        shelf.cursor_x += entry.width;
+        shelf.allocations += 1;
+
+        Some((shelf_index, x, y))
+    }
+}
+
+/// Single OpenGL texture backing an [`AtlasFamily`], with its own shelf
+/// state.
+struct AtlasTexture {
+    /// OpenGL texture ID.
+    id: GLuint,
+    /// Shelves allocated within this texture, bottom to top.
+    shelves: Vec<Shelf>,
+    /// CPU-side mirror of the texture's full pixels, present only when
+    /// [`GlFlavor::supports_partial_upload`] is `false`; every insert is
+    /// stitched into this buffer and the whole texture is re-uploaded via
+    /// `TexImage2D`, since some GLES2 drivers don't reliably support
+    /// partial `TexSubImage2D` updates.
+    cpu_buffer: Option<Vec<u8>>,
+}
+
+impl AtlasTexture {
+    /// Create a new atlas texture for the given content type.
+    fn new(content_type: ContentType, flavor: GlFlavor) -> Self {
+        let format = content_type.gl_format(flavor);
+
+        // `ATLAS_SIZE` is a power of two, so the `CLAMP_TO_EDGE` wrap mode
+        // and non-mipmapped `LINEAR` filtering used below are valid on
+        // GLES2 without any further fallback.
+        debug_assert_eq!(ATLAS_SIZE & (ATLAS_SIZE - 1), 0, "ATLAS_SIZE must be a power of two");
+
         let mut texture_id = 0;
         unsafe {
             gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
@@ -266,11 +720,11 @@ impl Atlas {
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA as i32,
+                format as i32,
                 ATLAS_SIZE,
                 ATLAS_SIZE,
                 0,
-                gl::RGBA,
+                format,
                 gl::UNSIGNED_BYTE,
                 ptr::null(),
             );
@@ -278,15 +732,147 @@ impl Atlas {
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
-        texture_id
+
+        let cpu_buffer = (!flavor.supports_partial_upload())
+            .then(|| vec![0u8; (ATLAS_SIZE * ATLAS_SIZE) as usize * content_type.channels()]);
+
+        Self { id: texture_id, shelves: Vec::new(), cpu_buffer }
+    }
+}
+
+/// Copy a `width`-wide, `src.len() / (width * channels)`-tall entry's pixels
+/// into a full `ATLAS_SIZE`-square buffer at `(x, y)`.
+fn blit(dst: &mut [u8], channels: usize, x: i32, y: i32, width: i32, src: &[u8]) {
+    let atlas_size = ATLAS_SIZE as usize;
+    let (x, width) = (x as usize, width as usize);
+    let row_bytes = width * channels;
+
+    for (row, src_row) in src.chunks_exact(row_bytes).enumerate() {
+        let dst_offset = ((y as usize + row) * atlas_size + x) * channels;
+        dst[dst_offset..dst_offset + row_bytes].copy_from_slice(src_row);
+    }
+}
+
+/// Content stored in an atlas subtexture's pixels.
+///
+/// Determines which texture family an entry lives in, and how the fragment
+/// shader should sample it: [`ContentType::Mask`] is single-channel coverage
+/// sampled as alpha, [`ContentType::Color`] is straight RGBA.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ContentType {
+    Mask,
+    Color,
+}
+
+impl ContentType {
+    /// GL format used as both the texture's internal and upload format,
+    /// chosen for compatibility with the detected [`GlFlavor`].
+    fn gl_format(self, flavor: GlFlavor) -> GLenum {
+        match (self, flavor) {
+            (Self::Mask, GlFlavor::Gl) => gl::RED,
+            // GLES2 has no core single-channel format equivalent to
+            // desktop GL's `GL_RED`; `GL_ALPHA` is the closest match
+            // without relying on an extension.
+            (Self::Mask, GlFlavor::Gles2) => gl::ALPHA,
+            (Self::Color, _) => gl::RGBA,
+        }
+    }
+
+    /// Number of bytes per pixel this content type's format uploads.
+    fn channels(self) -> usize {
+        match self {
+            Self::Mask => 1,
+            Self::Color => 4,
+        }
     }
 }
 
+/// OpenGL context flavor, detected at startup so the atlas can pick
+/// compatible texture formats and upload paths.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GlFlavor {
+    /// Desktop OpenGL.
+    Gl,
+    /// OpenGL ES 2.0, as found on e.g. embedded Wayland compositors.
+    Gles2,
+}
+
+impl GlFlavor {
+    /// Detect the flavor of the current GL context from `GL_VERSION`.
+    fn detect() -> Self {
+        let version = unsafe {
+            let ptr = gl::GetString(gl::VERSION);
+            if ptr.is_null() {
+                return Self::Gl;
+            }
+            CStr::from_ptr(ptr as *const _).to_string_lossy().into_owned()
+        };
+
+        if version.starts_with("OpenGL ES 2.") {
+            Self::Gles2
+        } else {
+            Self::Gl
+        }
+    }
+
+    /// Whether partial `TexSubImage2D` uploads of atlas entries can be
+    /// relied on, as opposed to re-uploading the whole texture with
+    /// `TexImage2D` on every insert.
+    fn supports_partial_upload(self) -> bool {
+        matches!(self, Self::Gl)
+    }
+}
+
+/// Horizontal strip of an [`AtlasTexture`] holding entries of similar height.
+struct Shelf {
+    /// Y position of this shelf's baseline.
+    y: i32,
+    /// Height of the tallest entry in this shelf.
+    height: i32,
+    /// X position for writing the next entry.
+    cursor_x: i32,
+    /// Number of live allocations handed out from this shelf.
+    allocations: usize,
+}
+
+/// Error returned when an entry doesn't fit into the atlas.
+#[derive(Copy, Clone, Debug)]
+struct AtlasFull;
+
+impl fmt::Display for AtlasFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("atlas is full")
+    }
+}
+
+impl Error for AtlasFull {}
+
+/// Opaque handle to an atlas allocation, used to free it again.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AllocId {
+    /// Texture family the allocation lives in.
+    content_type: ContentType,
+    /// Index of the allocation within that family.
+    index: usize,
+}
+
+/// Bookkeeping required to free an [`AllocId`]'s atlas space again.
+struct Allocation {
+    /// Index of the texture the allocation lives in.
+    texture: usize,
+    /// Index of the shelf within that texture.
+    shelf: usize,
+    /// Width of the allocation, currently unused beyond documentation.
+    #[allow(dead_code)]
+    width: i32,
+}
+
 /// Subtexture cached inside an [`Atlas`].
 #[derive(Copy, Clone, Debug)]
 pub struct GlSubTexture {
     pub texture_id: GLuint,
-    pub multicolor: bool,
+    pub alloc_id: AllocId,
+    pub content_type: ContentType,
     pub top: i16,
     pub left: i16,
     pub width: i16,
@@ -298,21 +884,20 @@ pub struct GlSubTexture {
     pub advance: (i32, i32),
 }
 
-fn rgb_to_rgba(rgb: &[u8]) -> Vec<u8> {
+/// Collapse an antialiased RGB coverage bitmap into a single-channel mask.
+///
+/// FreeType hands back monochrome glyphs as three identical RGB channels, so
+/// any one of them carries the full coverage value needed for the mask
+/// atlas.
+fn rgb_to_mask(rgb: &[u8]) -> Vec<u8> {
     let rgb_len = rgb.len();
     debug_assert_eq!(rgb_len % 3, 0);
 
-    let pixel_count = rgb_len / 3;
-    let mut rgba = vec![255; pixel_count * 4];
-
-    for (rgb, rgba) in rgb.chunks_exact(3).zip(rgba.chunks_exact_mut(4)) {
-        rgba[..3].copy_from_slice(rgb);
-    }
-
-    rgba
+    rgb.chunks_exact(3).map(|pixel| pixel[0]).collect()
 }
 
 /// Element stored in the texture atlas.
+#[derive(Clone)]
 struct AtlasEntry<'a> {
     buffer: Cow<'a, Vec<u8>>,
     width: i32,
@@ -341,7 +926,7 @@ impl AtlasEntry<'static> {
 impl<'a> From<&'a RasterizedGlyph> for AtlasEntry<'a> {
     fn from(glyph: &'a RasterizedGlyph) -> Self {
         let (buffer, multicolor) = match &glyph.buffer {
-            BitmapBuffer::Rgb(buffer) => (Cow::Owned(rgb_to_rgba(buffer)), false),
+            BitmapBuffer::Rgb(buffer) => (Cow::Owned(rgb_to_mask(buffer)), false),
             BitmapBuffer::Rgba(buffer) => (Cow::Borrowed(buffer), true),
         };
 
@@ -357,19 +942,72 @@ impl<'a> From<&'a RasterizedGlyph> for AtlasEntry<'a> {
     }
 }
 
+/// Insertion-ordered cache over atlas entries.
+///
+/// Evicts the least recently used entry once `capacity` is exceeded, moving
+/// an entry to the most-recently-used end of the order on every hit.
+struct LruCache {
+    entries: HashMap<CacheKey, GlSubTexture>,
+    /// Keys in usage order, least recently used first.
+    order: VecDeque<CacheKey>,
+    capacity: usize,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self { entries: HashMap::new(), order: VecDeque::new(), capacity }
+    }
+
+    /// Look up an entry, marking it as most recently used on a hit.
+    fn get(&mut self, key: &CacheKey) -> Option<GlSubTexture> {
+        let glyph = *self.entries.get(key)?;
+        self.touch(key);
+        Some(glyph)
+    }
+
+    /// Insert a new entry, evicting the least recently used one if the cache
+    /// is at capacity.
+    ///
+    /// Returns the evicted entry's [`AllocId`] so its atlas space can be
+    /// freed.
+    fn insert(&mut self, key: CacheKey, value: GlSubTexture) -> Option<AllocId> {
+        let evicted = if self.entries.len() >= self.capacity {
+            self.order
+                .pop_front()
+                .and_then(|oldest| self.entries.remove(&oldest))
+                .map(|entry| entry.alloc_id)
+        } else {
+            None
+        };
+
+        self.entries.insert(key, value);
+        self.order.push_back(key);
+
+        evicted
+    }
+
+    /// Move a key to the most-recently-used end of the eviction order.
+    fn touch(&mut self, key: &CacheKey) {
+        if let Some(index) = self.order.iter().position(|other| other == key) {
+            let key = self.order.remove(index).unwrap();
+            self.order.push_back(key);
+        }
+    }
+
+    /// Drop every cached entry without touching the atlas.
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
 /// Key for caching atlas entries.
 #[derive(Copy, Clone, Hash, PartialEq, Eq)]
 enum CacheKey {
-    Character(char),
+    Character(char, Flags),
     Svg(Svg),
 }
 
-impl From<char> for CacheKey {
-    fn from(c: char) -> Self {
-        Self::Character(c)
-    }
-}
-
 impl From<Svg> for CacheKey {
     fn from(svg: Svg) -> Self {
         Self::Svg(svg)
@@ -424,3 +1062,176 @@ impl Svg {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build an `AtlasEntry` with only the dimensions the shelf packer cares
+    /// about populated.
+    fn entry(width: i32, height: i32) -> AtlasEntry<'static> {
+        AtlasEntry {
+            buffer: Cow::Owned(Vec::new()),
+            width,
+            height,
+            top: 0,
+            left: 0,
+            advance: (0, 0),
+            multicolor: false,
+        }
+    }
+
+    /// An `AtlasTexture` with no backing GL texture, for exercising the
+    /// shelf packer without a GL context.
+    fn bare_texture() -> AtlasTexture {
+        AtlasTexture { id: 0, shelves: Vec::new(), cpu_buffer: None }
+    }
+
+    #[test]
+    fn allocate_opens_new_shelf_when_none_fit() {
+        let mut texture = bare_texture();
+
+        let (shelf, x, y) = AtlasFamily::allocate(&mut texture, &entry(10, 10)).unwrap();
+        assert_eq!((shelf, x, y), (0, 0, 0));
+
+        // Doesn't fit the existing height-10 shelf, so a new one opens above it.
+        let (shelf, x, y) = AtlasFamily::allocate(&mut texture, &entry(10, 20)).unwrap();
+        assert_eq!((shelf, x, y), (1, 0, 10));
+    }
+
+    #[test]
+    fn allocate_picks_shortest_shelf_with_room() {
+        let mut texture = bare_texture();
+
+        AtlasFamily::allocate(&mut texture, &entry(10, 30)).unwrap(); // Shelf 0, height 30.
+        AtlasFamily::allocate(&mut texture, &entry(10, 40)).unwrap(); // Shelf 1, height 40.
+
+        // Both shelves have room; the shorter one should be preferred.
+        let (shelf, ..) = AtlasFamily::allocate(&mut texture, &entry(10, 25)).unwrap();
+        assert_eq!(shelf, 0);
+    }
+
+    #[test]
+    fn allocate_fails_when_atlas_is_full() {
+        let mut texture = bare_texture();
+        AtlasFamily::allocate(&mut texture, &entry(ATLAS_SIZE, ATLAS_SIZE)).unwrap();
+        assert!(AtlasFamily::allocate(&mut texture, &entry(10, 10)).is_none());
+    }
+
+    #[test]
+    fn deallocate_reclaims_topmost_empty_shelf() {
+        let mut family = AtlasFamily {
+            content_type: ContentType::Mask,
+            flavor: GlFlavor::Gl,
+            textures: vec![bare_texture()],
+            allocations: HashMap::new(),
+            next_id: 0,
+        };
+
+        let (shelf_a, ..) = AtlasFamily::allocate(&mut family.textures[0], &entry(10, 10)).unwrap();
+        let id_a = family.next_id;
+        family.allocations.insert(id_a, Allocation { texture: 0, shelf: shelf_a, width: 10 });
+        family.next_id += 1;
+
+        let (shelf_b, ..) = AtlasFamily::allocate(&mut family.textures[0], &entry(10, 20)).unwrap();
+        let id_b = family.next_id;
+        family.allocations.insert(id_b, Allocation { texture: 0, shelf: shelf_b, width: 10 });
+        family.next_id += 1;
+
+        assert_eq!(family.textures[0].shelves.len(), 2);
+
+        // Freeing the topmost shelf's only allocation reclaims its space.
+        family.deallocate(AllocId { content_type: ContentType::Mask, index: id_b });
+        assert_eq!(family.textures[0].shelves.len(), 1);
+
+        // The remaining shelf is now topmost and empty, so it's reclaimed too.
+        family.deallocate(AllocId { content_type: ContentType::Mask, index: id_a });
+        assert_eq!(family.textures[0].shelves.len(), 0);
+
+        // `family` was built without a real GL texture bound; leak it rather
+        // than run `Drop`'s `glDeleteTextures` outside of a GL context.
+        mem::forget(family);
+    }
+
+    /// A `GlSubTexture` with an `AllocId` tagged by `index`, for telling
+    /// cache entries apart in assertions without a real atlas.
+    fn sub_texture(index: usize) -> GlSubTexture {
+        GlSubTexture {
+            texture_id: 0,
+            alloc_id: AllocId { content_type: ContentType::Mask, index },
+            content_type: ContentType::Mask,
+            top: 0,
+            left: 0,
+            width: 0,
+            height: 0,
+            uv_bot: 0.,
+            uv_left: 0.,
+            uv_width: 0.,
+            uv_height: 0.,
+            advance: (0, 0),
+        }
+    }
+
+    #[test]
+    fn lru_cache_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+
+        assert!(cache.insert(CacheKey::Character('a', Flags::empty()), sub_texture(0)).is_none());
+        assert!(cache.insert(CacheKey::Character('b', Flags::empty()), sub_texture(1)).is_none());
+
+        // Touch `a` so `b` becomes the least recently used entry.
+        assert!(cache.get(&CacheKey::Character('a', Flags::empty())).is_some());
+
+        let evicted = cache.insert(CacheKey::Character('c', Flags::empty()), sub_texture(2));
+        assert_eq!(evicted, Some(AllocId { content_type: ContentType::Mask, index: 1 }));
+
+        assert!(cache.get(&CacheKey::Character('b', Flags::empty())).is_none());
+        assert!(cache.get(&CacheKey::Character('a', Flags::empty())).is_some());
+        assert!(cache.get(&CacheKey::Character('c', Flags::empty())).is_some());
+    }
+
+    #[test]
+    fn lru_cache_clear_drops_every_entry() {
+        let mut cache = LruCache::new(2);
+        cache.insert(CacheKey::Character('a', Flags::empty()), sub_texture(0));
+
+        cache.clear();
+
+        assert!(cache.get(&CacheKey::Character('a', Flags::empty())).is_none());
+    }
+
+    #[test]
+    fn rgb_to_mask_keeps_one_channel_per_pixel() {
+        let rgb = [10, 10, 10, 20, 20, 20, 30, 30, 30];
+        assert_eq!(rgb_to_mask(&rgb), vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn embolden_dilates_with_right_and_bottom_neighbors() {
+        // 2x2, single channel; only the bottom-right pixel is set.
+        let mut buffer = vec![0, 0, 0, 10];
+        embolden(&mut buffer, 2, 2, 1);
+        assert_eq!(buffer, vec![0, 10, 10, 10]);
+    }
+
+    #[test]
+    fn shear_shifts_rows_right_by_a_quarter_of_the_remaining_height() {
+        // 3-wide, 4-tall, single channel; only the top row has content.
+        let mut buffer = vec![1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        shear(&mut buffer, 3, 4, 1);
+        // Row 0's shift is (4 - 0) / 4 == 1, so it moves right by one column,
+        // dropping the pixel that would fall off the right edge.
+        assert_eq!(buffer, vec![0, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn blit_copies_rows_into_full_atlas_buffer() {
+        let mut dst = vec![0u8; (ATLAS_SIZE * ATLAS_SIZE) as usize];
+        let src = [1, 2, 3, 4];
+        blit(&mut dst, 1, 5, 5, 2, &src);
+
+        let atlas_size = ATLAS_SIZE as usize;
+        assert_eq!(&dst[5 * atlas_size + 5..5 * atlas_size + 7], &[1, 2]);
+        assert_eq!(&dst[6 * atlas_size + 5..6 * atlas_size + 7], &[3, 4]);
+    }
+}